@@ -1,168 +1,726 @@
 #[cfg(target_os = "android")]
 use winit::platform::android::activity::AndroidApp;
 
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+mod renderer;
+use renderer::{RenderPhase, Renderer};
+
+use wgpu::util::DeviceExt;
 use winit::event_loop::EventLoop;
 use winit::{
     application::ApplicationHandler,
-    event::{DeviceEvent, StartCause, WindowEvent},
-    event_loop::ActiveEventLoop,
+    event::{DeviceEvent, ElementState, StartCause, WindowEvent},
+    event_loop::{ActiveEventLoop, EventLoopProxy},
+    keyboard::Key,
     window::{WindowAttributes, WindowId},
 };
 
+#[cfg(not(any(target_os = "android", target_arch = "wasm32")))]
+use std::path::Path;
+
+/// User events delivered through the `EventLoop` proxy.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AppEvent {
+    /// The watched WGSL source file changed on disk.
+    ShaderChanged,
+    /// A render command parsed from a `window.ipc.postMessage(...)` call in
+    /// the control-panel webview.
+    GpuCommand(RenderCommand),
+}
+
+/// Render commands the control-panel HTML sends over `window.ipc.postMessage`,
+/// deserialized from the JSON body of the IPC request.
+#[cfg(not(target_os = "android"))]
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RenderCommand {
+    /// Sets the uniform color the fragment shader fills the triangle with.
+    FillColor { r: f32, g: f32, b: f32, a: f32 },
+    /// Replaces the triangle's three vertex positions, in clip space.
+    TrianglePositions { positions: [[f32; 2]; 3] },
+    /// Shows or hides the `Opaque` triangle pass without unregistering it.
+    TogglePass { enabled: bool },
+}
+
+/// On Android there's no webview to send commands, but [`AppEvent`] still
+/// needs a concrete `RenderCommand` type to carry.
+#[cfg(target_os = "android")]
+#[derive(Debug, Clone, PartialEq)]
+pub enum RenderCommand {}
+
+/// GPU-visible scene state mutated by [`RenderCommand`]s and read by the
+/// triangle shader through a uniform buffer bound at `@group(0) @binding(0)`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct SceneUniforms {
+    color: [f32; 4],
+    positions: [[f32; 4]; 3],
+}
+
+impl Default for SceneUniforms {
+    fn default() -> Self {
+        Self {
+            color: [1.0, 0.0, 0.0, 1.0],
+            positions: [
+                [0.0, 0.5, 0.0, 0.0],
+                [-0.5, -0.5, 0.0, 0.0],
+                [0.5, -0.5, 0.0, 0.0],
+            ],
+        }
+    }
+}
+
+#[cfg(test)]
+mod render_command_tests {
+    use super::*;
+
+    #[cfg(not(target_os = "android"))]
+    #[test]
+    fn parses_fill_color_command() {
+        let json = r#"{"kind":"fill_color","r":0.1,"g":0.2,"b":0.3,"a":1.0}"#;
+        let command: RenderCommand = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            command,
+            RenderCommand::FillColor {
+                r: 0.1,
+                g: 0.2,
+                b: 0.3,
+                a: 1.0
+            }
+        );
+    }
+
+    #[cfg(not(target_os = "android"))]
+    #[test]
+    fn parses_triangle_positions_command() {
+        let json =
+            r#"{"kind":"triangle_positions","positions":[[0.0,0.5],[-0.5,-0.5],[0.5,-0.5]]}"#;
+        let command: RenderCommand = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            command,
+            RenderCommand::TrianglePositions {
+                positions: [[0.0, 0.5], [-0.5, -0.5], [0.5, -0.5]]
+            }
+        );
+    }
+
+    #[cfg(not(target_os = "android"))]
+    #[test]
+    fn parses_toggle_pass_command() {
+        let json = r#"{"kind":"toggle_pass","enabled":false}"#;
+        let command: RenderCommand = serde_json::from_str(json).unwrap();
+        assert_eq!(command, RenderCommand::TogglePass { enabled: false });
+    }
+
+    #[cfg(not(target_os = "android"))]
+    #[test]
+    fn rejects_an_unknown_kind() {
+        let json = r#"{"kind":"not_a_real_command"}"#;
+        assert!(serde_json::from_str::<RenderCommand>(json).is_err());
+    }
+
+    #[test]
+    fn scene_uniforms_size_matches_the_wgsl_struct() {
+        // `Uniforms` in triangle.wgsl is a vec4<f32> plus array<vec4<f32>, 3>,
+        // i.e. 4 vec4s of 16 bytes each; a mismatch here means the Rust and
+        // WGSL struct layouts have drifted apart.
+        assert_eq!(std::mem::size_of::<SceneUniforms>(), 4 * 16);
+    }
+}
+
+/// Path to the live WGSL source, watched for hot-reload on desktop builds.
+#[cfg(not(any(target_os = "android", target_arch = "wasm32")))]
+const SHADER_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/shaders/triangle.wgsl");
+
+/// Baked-in fallback used when the shader file can't be read (and always,
+/// on Android/wasm where filesystem watching isn't available).
+const DEFAULT_SHADER_SRC: &str = include_str!("../shaders/triangle.wgsl");
+
+/// The webview's control panel: posts [`RenderCommand`] JSON to
+/// `window.ipc.postMessage` so `resumed`'s IPC handler can forward each one
+/// through the `EventLoop` proxy as an [`AppEvent::GpuCommand`].
+#[cfg(not(target_os = "android"))]
+const CONTROL_PANEL_HTML: &str = r##"<html>
+    <body style="background-color:rgba(87,87,87,0.5); color:#eee; font:12px monospace; margin:0; padding:6px;">
+        <div style="display:flex; flex-direction:column; gap:4px;">
+            <label>Color <input id="color" type="color" value="#ff0000"></label>
+            <label><input id="toggle" type="checkbox" checked> Show triangle</label>
+            <button id="flip">Flip triangle</button>
+        </div>
+        <script>
+            function send(command) {
+                window.ipc.postMessage(JSON.stringify(command));
+            }
+            document.getElementById('color').addEventListener('input', (event) => {
+                const hex = event.target.value;
+                send({
+                    kind: 'fill_color',
+                    r: parseInt(hex.substr(1, 2), 16) / 255,
+                    g: parseInt(hex.substr(3, 2), 16) / 255,
+                    b: parseInt(hex.substr(5, 2), 16) / 255,
+                    a: 1.0,
+                });
+            });
+            document.getElementById('toggle').addEventListener('change', (event) => {
+                send({ kind: 'toggle_pass', enabled: event.target.checked });
+            });
+            let flipped = false;
+            document.getElementById('flip').addEventListener('click', () => {
+                flipped = !flipped;
+                const positions = flipped
+                    ? [[0.0, -0.5], [-0.5, 0.5], [0.5, 0.5]]
+                    : [[0.0, 0.5], [-0.5, -0.5], [0.5, -0.5]];
+                send({ kind: 'triangle_positions', positions });
+            });
+        </script>
+    </body>
+</html>"##;
+
+fn load_shader_source() -> String {
+    #[cfg(not(any(target_os = "android", target_arch = "wasm32")))]
+    {
+        std::fs::read_to_string(SHADER_PATH).unwrap_or_else(|err| {
+            log::warn!("Falling back to the built-in shader, couldn't read {SHADER_PATH}: {err}");
+            DEFAULT_SHADER_SRC.to_string()
+        })
+    }
+    #[cfg(any(target_os = "android", target_arch = "wasm32"))]
+    {
+        DEFAULT_SHADER_SRC.to_string()
+    }
+}
+
+/// Spawns a filesystem watcher on [`SHADER_PATH`]'s parent directory that
+/// sends [`AppEvent::ShaderChanged`] through `proxy` whenever the shader
+/// file is created, modified, or replaced by an atomic save.
+#[cfg(not(any(target_os = "android", target_arch = "wasm32")))]
+fn spawn_shader_watcher(proxy: EventLoopProxy<AppEvent>) -> Option<notify::RecommendedWatcher> {
+    use notify::{RecursiveMode, Watcher};
+
+    // Editors that save atomically (write-temp + rename, e.g. vim with
+    // `backupcopy=no`, VS Code) replace the shader's inode on every save, so
+    // watching `SHADER_PATH` itself only ever catches the first edit before
+    // the watch is left pointing at a deleted inode. Watch the parent
+    // directory instead and filter by file name so renames and recreations
+    // of the shader keep firing `AppEvent::ShaderChanged`.
+    let shader_path = Path::new(SHADER_PATH);
+    let shader_dir = shader_path.parent().unwrap_or_else(|| Path::new("."));
+    let shader_file_name = shader_path.file_name();
+
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        let Ok(event) = res else { return };
+        let is_relevant_kind =
+            event.kind.is_create() || event.kind.is_modify() || event.kind.is_remove();
+        let touches_shader = event
+            .paths
+            .iter()
+            .any(|p| p.file_name() == shader_file_name);
+        if is_relevant_kind && touches_shader {
+            let _ = proxy.send_event(AppEvent::ShaderChanged);
+        }
+    })
+    .inspect_err(|err| log::warn!("Shader hot-reload disabled, couldn't start watcher: {err}"))
+    .ok()?;
+
+    watcher
+        .watch(shader_dir, RecursiveMode::NonRecursive)
+        .inspect_err(|err| {
+            log::warn!("Shader hot-reload disabled, couldn't watch {shader_dir:?}: {err}")
+        })
+        .ok()?;
+
+    Some(watcher)
+}
+
+/// Builds the bind group layout for the scene uniform buffer the shader
+/// reads at `@group(0) @binding(0)`.
+fn build_scene_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Scene Uniforms Layout"),
+        entries: &[wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        }],
+    })
+}
+
+/// Builds the shader module and render pipeline from WGSL `source`, wired up
+/// to `bind_group_layout` so the shader can read the scene uniform buffer.
+fn build_pipeline(
+    device: &wgpu::Device,
+    format: wgpu::TextureFormat,
+    source: &str,
+    bind_group_layout: &wgpu::BindGroupLayout,
+) -> wgpu::RenderPipeline {
+    let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Triangle Shader"),
+        source: wgpu::ShaderSource::Wgsl(source.to_string().into()),
+    });
+
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None,
+        bind_group_layouts: &[bind_group_layout],
+        push_constant_ranges: &[],
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: None,
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader_module,
+            entry_point: Some("vs_main"),
+            buffers: &[],
+            compilation_options: Default::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader_module,
+            entry_point: Some("fs_main"),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(wgpu::BlendState::REPLACE),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: Default::default(),
+        }),
+        primitive: Default::default(),
+        depth_stencil: None,
+        multisample: Default::default(),
+        multiview: None,
+        cache: None,
+    })
+}
+
+/// Registers `pipeline` as the `Opaque` phase, which clears the surface to
+/// green then draws the triangle with `bind_group` bound, unless `enabled`
+/// has been toggled off by a [`RenderCommand::TogglePass`].
+fn register_triangle_pass(
+    renderer: &mut Renderer,
+    pipeline: Arc<wgpu::RenderPipeline>,
+    bind_group: Arc<wgpu::BindGroup>,
+    enabled: Arc<AtomicBool>,
+) {
+    renderer.register_pass(
+        RenderPhase::Opaque,
+        Some(wgpu::Color::GREEN),
+        move |rpass| {
+            if !enabled.load(Ordering::Relaxed) {
+                return;
+            }
+            rpass.set_pipeline(&pipeline);
+            rpass.set_bind_group(0, &*bind_group, &[]);
+            rpass.draw(0..3, 0..1);
+        },
+    );
+}
+
 #[cfg(not(target_os = "android"))]
 use wry::{
     dpi::{LogicalPosition, LogicalSize},
     Rect, WebViewBuilder,
 };
 
+/// Selects which `wgpu::Limits` are requested from the adapter.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LimitsProfile {
+    /// Downlevel-compatible limits so the same binary runs on low-end
+    /// Android GLES devices and `wasm32` WebGPU/WebGL2 targets.
+    #[default]
+    DownlevelWebGl2,
+    /// The adapter's native limits, for builds targeting capable desktop
+    /// hardware that want more than the downlevel defaults allow.
+    Native,
+}
+
+/// Number of recent frames kept for the rolling FPS stats.
+const FRAME_STATS_WINDOW: usize = 120;
+
+/// Logical height of macOS's traffic-light window controls, used to inset
+/// the embedded webview below them when the titlebar is unified with the
+/// transparent content view (see `resumed` and [`AppHandler::with_macos_titlebar_inset`]).
+#[cfg(target_os = "macos")]
+const MACOS_TRAFFIC_LIGHT_INSET: i32 = 28;
+
+/// Tracks inter-frame deltas over a rolling window and reports min/median/max FPS.
+struct FrameStats {
+    last_frame: Option<Instant>,
+    deltas: VecDeque<Duration>,
+}
+
+impl FrameStats {
+    fn new() -> Self {
+        Self {
+            last_frame: None,
+            deltas: VecDeque::with_capacity(FRAME_STATS_WINDOW),
+        }
+    }
+
+    fn record_frame(&mut self) {
+        let now = Instant::now();
+        if let Some(last) = self.last_frame {
+            if self.deltas.len() == FRAME_STATS_WINDOW {
+                self.deltas.pop_front();
+            }
+            self.deltas.push_back(now - last);
+        }
+        self.last_frame = Some(now);
+    }
+
+    /// Returns `(min_fps, median_fps, max_fps)` over the current window, or
+    /// `None` until at least one frame delta has been recorded.
+    fn min_median_max_fps(&self) -> Option<(f64, f64, f64)> {
+        if self.deltas.is_empty() {
+            return None;
+        }
+        let mut secs: Vec<f64> = self.deltas.iter().map(Duration::as_secs_f64).collect();
+        secs.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        let fps_of = |s: f64| if s > 0.0 { 1.0 / s } else { 0.0 };
+        let mid = secs.len() / 2;
+        let median_secs = if secs.len() % 2 == 0 {
+            (secs[mid - 1] + secs[mid]) / 2.0
+        } else {
+            secs[mid]
+        };
+
+        // Longest delta -> lowest FPS, shortest delta -> highest FPS.
+        Some((
+            fps_of(*secs.last().unwrap()),
+            fps_of(median_secs),
+            fps_of(secs[0]),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod frame_stats_tests {
+    use super::*;
+
+    #[test]
+    fn no_frames_recorded_yields_none() {
+        let stats = FrameStats::new();
+        assert_eq!(stats.min_median_max_fps(), None);
+    }
+
+    #[test]
+    fn a_single_frame_has_no_delta_yet() {
+        let mut stats = FrameStats::new();
+        stats.record_frame();
+        assert_eq!(stats.min_median_max_fps(), None);
+    }
+
+    #[test]
+    fn even_window_averages_the_two_middle_deltas() {
+        let mut stats = FrameStats::new();
+        stats.deltas.extend([
+            Duration::from_millis(10),
+            Duration::from_millis(40),
+            Duration::from_millis(30),
+            Duration::from_millis(20),
+        ]);
+
+        let (min_fps, median_fps, max_fps) = stats.min_median_max_fps().unwrap();
+        // Sorted deltas: 10/20/30/40ms; median of the middle two (20, 30) is
+        // 25ms -> 40fps. Longest delta (40ms) is the min FPS, shortest
+        // (10ms) is the max FPS.
+        assert!((min_fps - 25.0).abs() < 1e-9);
+        assert!((median_fps - 40.0).abs() < 1e-9);
+        assert!((max_fps - 100.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn odd_window_uses_the_middle_delta() {
+        let mut stats = FrameStats::new();
+        stats.deltas.extend([
+            Duration::from_millis(20),
+            Duration::from_millis(10),
+            Duration::from_millis(40),
+        ]);
+
+        let (_, median_fps, _) = stats.min_median_max_fps().unwrap();
+        assert!((median_fps - 50.0).abs() < 1e-9);
+    }
+}
+
 pub struct AppHandler<'a> {
+    instance: Option<wgpu::Instance>,
+    adapter: Option<wgpu::Adapter>,
     surface: Option<wgpu::Surface<'a>>,
-    device: Option<wgpu::Device>,
-    queue: Option<wgpu::Queue>,
-    render_pipeline: Option<wgpu::RenderPipeline>,
+    device: Option<Arc<wgpu::Device>>,
+    queue: Option<Arc<wgpu::Queue>>,
+    renderer: Option<Renderer>,
     config: Option<wgpu::SurfaceConfiguration>,
+    limits_profile: LimitsProfile,
+    present_modes: Vec<wgpu::PresentMode>,
+    present_mode_index: usize,
+    frame_stats: FrameStats,
+    event_proxy: EventLoopProxy<AppEvent>,
+    #[cfg(not(any(target_os = "android", target_arch = "wasm32")))]
+    shader_watcher: Option<notify::RecommendedWatcher>,
+    scene: SceneUniforms,
+    scene_uniform_buffer: Option<wgpu::Buffer>,
+    scene_bind_group_layout: Option<Arc<wgpu::BindGroupLayout>>,
+    scene_bind_group: Option<Arc<wgpu::BindGroup>>,
+    triangle_enabled: Arc<AtomicBool>,
+    #[cfg(target_os = "macos")]
+    inset_webview_below_titlebar: bool,
 
     #[cfg(not(target_os = "android"))]
     webview: Option<wry::WebView>,
 }
 
 impl<'a> AppHandler<'a> {
-    pub fn new() -> Self {
+    pub fn new(event_proxy: EventLoopProxy<AppEvent>) -> Self {
         Self {
+            instance: None,
+            adapter: None,
             surface: None,
             device: None,
             queue: None,
-            render_pipeline: None,
+            renderer: None,
             config: None,
+            limits_profile: LimitsProfile::default(),
+            present_modes: Vec::new(),
+            present_mode_index: 0,
+            frame_stats: FrameStats::new(),
+            event_proxy,
+            #[cfg(not(any(target_os = "android", target_arch = "wasm32")))]
+            shader_watcher: None,
+            scene: SceneUniforms::default(),
+            scene_uniform_buffer: None,
+            scene_bind_group_layout: None,
+            scene_bind_group: None,
+            triangle_enabled: Arc::new(AtomicBool::new(true)),
+            // Default to inset: `resumed`'s macOS branch unconditionally
+            // unifies the titlebar with the content view, so without this
+            // the control-panel webview would sit under the traffic lights
+            // out of the box.
+            #[cfg(target_os = "macos")]
+            inset_webview_below_titlebar: true,
             #[cfg(not(target_os = "android"))]
             webview: None,
         }
     }
+
+    /// Opt into the adapter's native limits instead of the downlevel
+    /// WebGL2-compatible defaults, for builds that only target capable
+    /// desktop hardware.
+    pub fn with_limits_profile(mut self, profile: LimitsProfile) -> Self {
+        self.limits_profile = profile;
+        self
+    }
+
+    /// Controls whether the embedded webview is pushed down by
+    /// [`MACOS_TRAFFIC_LIGHT_INSET`] to clear the traffic-light controls that
+    /// `resumed`'s unified-titlebar branch extends the content view
+    /// underneath. Defaults to `true`; pass `false` to let the webview
+    /// extend all the way under the titlebar instead. Only available on
+    /// macOS builds.
+    #[cfg(target_os = "macos")]
+    pub fn with_macos_titlebar_inset(mut self, inset: bool) -> Self {
+        self.inset_webview_below_titlebar = inset;
+        self
+    }
+
+    /// Cycles `self.config.present_mode` through the surface's supported
+    /// present modes (e.g. Fifo/Mailbox/Immediate) and reconfigures.
+    fn cycle_present_mode(&mut self) {
+        if self.present_modes.is_empty() {
+            return;
+        }
+        self.present_mode_index = (self.present_mode_index + 1) % self.present_modes.len();
+        if let (Some(surface), Some(device), Some(config)) =
+            (&self.surface, &self.device, &mut self.config)
+        {
+            config.present_mode = self.present_modes[self.present_mode_index];
+            surface.configure(device, config);
+            log::info!("Present mode: {:?}", config.present_mode);
+        }
+    }
 }
 
-impl<'a> ApplicationHandler<()> for AppHandler<'a> {
+impl<'a> ApplicationHandler<AppEvent> for AppHandler<'a> {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-        let attr = WindowAttributes::default()
+        // On Android the platform tears down the `ANativeWindow` on every
+        // suspend and hands back a brand-new one on resume, so only the
+        // surface is rebuilt here; the instance/adapter/device/queue are
+        // created once and cached for the lifetime of the app.
+        let mut attr = WindowAttributes::default()
             .with_title("Snow Player")
             .with_transparent(true);
+
+        // The native titlebar is otherwise opaque and sits on top of the
+        // wgpu-cleared surface, which breaks the transparency requested
+        // above; unify it with the content view so the clear color and the
+        // webview extend underneath it.
+        #[cfg(target_os = "macos")]
+        {
+            use winit::platform::macos::WindowAttributesExtMacOS;
+            attr = attr
+                .with_titlebar_transparent(true)
+                .with_fullsize_content_view(true);
+        }
+
         let window = event_loop.create_window(attr).unwrap();
         let size = window.inner_size();
 
         #[cfg(not(target_os = "android"))]
         {
+            let ipc_proxy = self.event_proxy.clone();
+
+            #[cfg(target_os = "macos")]
+            let webview_top = if self.inset_webview_below_titlebar {
+                MACOS_TRAFFIC_LIGHT_INSET
+            } else {
+                0
+            };
+            #[cfg(not(target_os = "macos"))]
+            let webview_top = 0;
+
             self.webview = Some(
                 WebViewBuilder::new()
                     .with_bounds(Rect {
-                        position: LogicalPosition::new(0, 0).into(),
-                        size: LogicalSize::new(200, 200).into(),
+                        position: LogicalPosition::new(0, webview_top).into(),
+                        size: LogicalSize::new(220, 160).into(),
                     })
                     .with_transparent(true)
-                    .with_html(
-                        r#"<html>
-                    <body style="background-color:rgba(87,87,87,0.5);"></body>
-                    <script>
-                        window.onload = function() {
-                            document.body.innerText = `سلام, ${navigator.userAgent}`;
-                        };
-                    </script>
-                </html>"#,
-                    )
+                    .with_ipc_handler(move |request: wry::http::Request<String>| {
+                        match serde_json::from_str::<RenderCommand>(request.body()) {
+                            Ok(command) => {
+                                let _ = ipc_proxy.send_event(AppEvent::GpuCommand(command));
+                            }
+                            Err(err) => log::warn!("Ignoring malformed IPC message: {err}"),
+                        }
+                    })
+                    .with_html(CONTROL_PANEL_HTML)
                     .build_as_child(&window)
                     .unwrap(),
             );
         }
 
-        let instance = wgpu::Instance::default();
+        let instance = self.instance.get_or_insert_with(wgpu::Instance::default);
         let surface = instance.create_surface(window).unwrap();
 
-        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
-            power_preference: wgpu::PowerPreference::HighPerformance,
-            compatible_surface: Some(&surface),
-            force_fallback_adapter: false,
-        }))
-        .unwrap();
-
-        let (device, queue) =
-            pollster::block_on(adapter.request_device(&Default::default())).unwrap();
+        let first_init = self.device.is_none();
+        if first_init {
+            let adapter =
+                pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+                    power_preference: wgpu::PowerPreference::HighPerformance,
+                    compatible_surface: Some(&surface),
+                    force_fallback_adapter: false,
+                }))
+                .unwrap();
+
+            let required_limits = match self.limits_profile {
+                LimitsProfile::DownlevelWebGl2 => {
+                    wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits())
+                }
+                LimitsProfile::Native => adapter.limits(),
+            };
 
-        log::info!("Adapter: {:?}", adapter.get_info());
-        log::info!("Device: {:?}", device.limits());
+            let (device, queue) =
+                pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+                    label: None,
+                    required_features: wgpu::Features::empty(),
+                    required_limits,
+                    memory_hints: wgpu::MemoryHints::default(),
+                    trace: wgpu::Trace::Off,
+                }))
+                .unwrap();
+
+            log::info!("Adapter: {:?}", adapter.get_info());
+            log::info!("Device: {:?}", device.limits());
+
+            self.adapter = Some(adapter);
+            self.device = Some(Arc::new(device));
+            self.queue = Some(Arc::new(queue));
+        }
 
-        let surface_caps = surface.get_capabilities(&adapter);
-        let format = surface_caps.formats[0];
+        let adapter = self.adapter.as_ref().unwrap();
+        let device = self.device.as_ref().unwrap();
+
+        let surface_caps = surface.get_capabilities(adapter);
+        // Not every backend reports an sRGB format first (GLES/WebGL2
+        // often don't), so pick one explicitly and fall back otherwise.
+        let format = surface_caps
+            .formats
+            .iter()
+            .copied()
+            .find(|f| f.is_srgb())
+            .unwrap_or(surface_caps.formats[0]);
+
+        self.present_modes = surface_caps.present_modes.clone();
+        if self.present_mode_index >= self.present_modes.len() {
+            self.present_mode_index = 0;
+        }
 
         let config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format,
             width: size.width,
             height: size.height,
-            present_mode: surface_caps.present_modes[0],
+            present_mode: self.present_modes[self.present_mode_index],
             alpha_mode: surface_caps.alpha_modes[0],
             view_formats: vec![],
             desired_maximum_frame_latency: 1,
         };
-        surface.configure(&device, &config);
-
-        let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Inline Shader"),
-            source: wgpu::ShaderSource::Wgsl(
-                r#"
-                    @vertex
-                    fn vs_main(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
-                        var positions = array<vec2<f32>, 3>(
-                            vec2<f32>(0.0,  0.5),
-                            vec2<f32>(-0.5, -0.5),
-                            vec2<f32>(0.5, -0.5)
-                        );
-                        let pos = positions[vertex_index];
-                        return vec4<f32>(pos, 0.0, 1.0);
-                    }
+        surface.configure(device, &config);
+
+        if first_init {
+            let queue = self.queue.as_ref().unwrap();
+
+            let bind_group_layout = Arc::new(build_scene_bind_group_layout(device));
+            let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Scene Uniforms"),
+                contents: bytemuck::bytes_of(&self.scene),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+            let bind_group = Arc::new(device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Scene Uniforms Bind Group"),
+                layout: &bind_group_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                }],
+            }));
+
+            let mut renderer = Renderer::new(Arc::clone(device), Arc::clone(queue));
+            let source = load_shader_source();
+            let pipeline = Arc::new(build_pipeline(
+                device,
+                config.format,
+                &source,
+                &bind_group_layout,
+            ));
+            register_triangle_pass(
+                &mut renderer,
+                pipeline,
+                Arc::clone(&bind_group),
+                Arc::clone(&self.triangle_enabled),
+            );
+            self.renderer = Some(renderer);
 
-                    @fragment
-                    fn fs_main() -> @location(0) vec4<f32> {
-                        return vec4<f32>(1.0, 0.0, 0.0, 1.0);
-                    }
-                "#.into(),
-            ),
-        });
-
-        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: None,
-            bind_group_layouts: &[],
-            push_constant_ranges: &[],
-        });
-
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: None,
-            layout: Some(&pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader_module,
-                entry_point: Some("vs_main"),
-                buffers: &[],
-                compilation_options: Default::default(),
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader_module,
-                entry_point: Some("fs_main"),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-                compilation_options: Default::default(),
-            }),
-            primitive: Default::default(),
-            depth_stencil: None,
-            multisample: Default::default(),
-            multiview: None,
-            cache: None,
-        });
+            self.scene_bind_group_layout = Some(bind_group_layout);
+            self.scene_bind_group = Some(bind_group);
+            self.scene_uniform_buffer = Some(uniform_buffer);
+
+            #[cfg(not(any(target_os = "android", target_arch = "wasm32")))]
+            {
+                self.shader_watcher = spawn_shader_watcher(self.event_proxy.clone());
+            }
+        }
 
         self.surface = Some(surface);
-        self.device = Some(device);
-        self.queue = Some(queue);
         self.config = Some(config);
-        self.render_pipeline = Some(render_pipeline);
     }
 
     fn window_event(&mut self, event_loop: &ActiveEventLoop, _: WindowId, event: WindowEvent) {
@@ -177,21 +735,34 @@ impl<'a> ApplicationHandler<()> for AppHandler<'a> {
                 }
                 WindowEvent::RedrawRequested => self.draw_frame(),
                 WindowEvent::CloseRequested => event_loop.exit(),
+                WindowEvent::KeyboardInput { event, .. } => {
+                    if event.state == ElementState::Pressed
+                        && matches!(&event.logical_key, Key::Character(c) if c.eq_ignore_ascii_case("v"))
+                    {
+                        self.cycle_present_mode();
+                    }
+                }
                 _ => {}
             }
         }
     }
 
     fn about_to_wait(&mut self, _: &ActiveEventLoop) {
-        self.draw_frame();
+        if self.surface.is_some() {
+            self.draw_frame();
+        }
     }
 
     fn new_events(&mut self, event_loop: &ActiveEventLoop, cause: StartCause) {
         let _ = (event_loop, cause);
     }
 
-    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: ()) {
-        let _ = (event_loop, event);
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: AppEvent) {
+        let _ = event_loop;
+        match event {
+            AppEvent::ShaderChanged => self.reload_shader(),
+            AppEvent::GpuCommand(command) => self.apply_gpu_command(command),
+        }
     }
 
     fn device_event(
@@ -205,6 +776,10 @@ impl<'a> ApplicationHandler<()> for AppHandler<'a> {
 
     fn suspended(&mut self, event_loop: &ActiveEventLoop) {
         let _ = event_loop;
+        // The Android activity is about to destroy our `ANativeWindow`; drop
+        // the surface now so we don't hold a handle to a dead window, and
+        // stop drawing until `resumed` hands us a fresh one.
+        self.surface = None;
     }
 
     fn exiting(&mut self, event_loop: &ActiveEventLoop) {
@@ -219,44 +794,120 @@ impl<'a> ApplicationHandler<()> for AppHandler<'a> {
 // Private method implementation for AppHandler
 impl<'a> AppHandler<'a> {
     fn draw_frame(&mut self) {
-        if let (Some(surface), Some(device), Some(queue), Some(pipeline), Some(config)) = (
+        if let (Some(renderer), Some(surface), Some(device), Some(config)) = (
+            &self.renderer,
             &self.surface,
             &self.device,
-            &self.queue,
-            &self.render_pipeline,
-            &self.config,
+            &mut self.config,
         ) {
-            let output = match surface.get_current_texture() {
-                Ok(frame) => frame,
-                Err(_) => {
+            match renderer.render(surface, config.format) {
+                Ok(()) => {}
+                Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
                     surface.configure(device, config);
-                    surface.get_current_texture().unwrap()
                 }
-            };
-            let view = output
-                .texture
-                .create_view(&wgpu::TextureViewDescriptor::default());
-            let mut encoder =
-                device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
-            {
-                let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                    label: None,
-                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                        view: &view,
-                        resolve_target: None,
-                        ops: wgpu::Operations {
-                            load: wgpu::LoadOp::Clear(wgpu::Color::GREEN),
-                            store: wgpu::StoreOp::Store,
-                        },
-                    })],
-                    depth_stencil_attachment: None,
-                    ..Default::default()
-                });
-                rpass.set_pipeline(pipeline);
-                rpass.draw(0..3, 0..1);
+                Err(wgpu::SurfaceError::OutOfMemory) => {
+                    log::error!("Surface out of memory, cannot continue rendering");
+                }
+                Err(err) => log::warn!("Dropped frame: {err:?}"),
             }
-            queue.submit(Some(encoder.finish()));
-            output.present();
+        }
+
+        self.frame_stats.record_frame();
+        self.push_frame_stats_to_webview();
+    }
+
+    #[cfg(not(target_os = "android"))]
+    fn push_frame_stats_to_webview(&self) {
+        if let (Some(webview), Some((min_fps, median_fps, max_fps))) =
+            (&self.webview, self.frame_stats.min_median_max_fps())
+        {
+            let js = format!(
+                "(function(){{\
+                    var el = document.getElementById('wgpu-stats');\
+                    if (!el) {{\
+                        el = document.createElement('div');\
+                        el.id = 'wgpu-stats';\
+                        el.style.cssText = 'position:fixed;top:4px;left:4px;color:#0f0;font:12px monospace;z-index:9999;pointer-events:none;';\
+                        document.body.appendChild(el);\
+                    }}\
+                    el.textContent = 'FPS min/median/max: {min_fps:.1}/{median_fps:.1}/{max_fps:.1}';\
+                }})();"
+            );
+            let _ = webview.evaluate_script(&js);
+        }
+    }
+
+    #[cfg(target_os = "android")]
+    fn push_frame_stats_to_webview(&self) {}
+
+    /// Recompiles the shader and re-registers the `Opaque` pass from
+    /// [`SHADER_PATH`]. Validation errors are caught via an error scope so a
+    /// bad edit logs a message and leaves the last-known-good pipeline in
+    /// place instead of panicking.
+    fn reload_shader(&mut self) {
+        let (Some(renderer), Some(device), Some(config), Some(bind_group_layout), Some(bind_group)) = (
+            &mut self.renderer,
+            &self.device,
+            &self.config,
+            &self.scene_bind_group_layout,
+            &self.scene_bind_group,
+        ) else {
+            return;
+        };
+
+        let source = load_shader_source();
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let pipeline = Arc::new(build_pipeline(
+            device,
+            config.format,
+            &source,
+            bind_group_layout,
+        ));
+
+        match pollster::block_on(device.pop_error_scope()) {
+            Some(err) => log::error!("Shader reload failed, keeping previous pipeline: {err}"),
+            None => {
+                register_triangle_pass(
+                    renderer,
+                    pipeline,
+                    Arc::clone(bind_group),
+                    Arc::clone(&self.triangle_enabled),
+                );
+                log::info!("Shader hot-reloaded");
+            }
+        }
+    }
+
+    /// Applies a [`RenderCommand`] from the control-panel webview by
+    /// mutating [`AppHandler::scene`] and re-uploading it to the GPU, or by
+    /// flipping [`AppHandler::triangle_enabled`] for [`RenderCommand::TogglePass`].
+    #[cfg(not(target_os = "android"))]
+    fn apply_gpu_command(&mut self, command: RenderCommand) {
+        match command {
+            RenderCommand::FillColor { r, g, b, a } => {
+                self.scene.color = [r, g, b, a];
+                self.write_scene_uniforms();
+            }
+            RenderCommand::TrianglePositions { positions } => {
+                for (slot, [x, y]) in self.scene.positions.iter_mut().zip(positions) {
+                    *slot = [x, y, 0.0, 0.0];
+                }
+                self.write_scene_uniforms();
+            }
+            RenderCommand::TogglePass { enabled } => {
+                self.triangle_enabled.store(enabled, Ordering::Relaxed);
+            }
+        }
+    }
+
+    #[cfg(target_os = "android")]
+    fn apply_gpu_command(&mut self, command: RenderCommand) {
+        match command {}
+    }
+
+    fn write_scene_uniforms(&self) {
+        if let (Some(queue), Some(buffer)) = (&self.queue, &self.scene_uniform_buffer) {
+            queue.write_buffer(buffer, 0, bytemuck::bytes_of(&self.scene));
         }
     }
 }
@@ -275,15 +926,18 @@ fn android_main(app: AndroidApp) {
 
     log::info!("Starting Snow Player on Android");
 
-    let event_loop = EventLoop::builder().with_android_app(app).build().unwrap();
-    let mut handler = AppHandler::new();
+    let event_loop = EventLoop::<AppEvent>::with_user_event()
+        .with_android_app(app)
+        .build()
+        .unwrap();
+    let mut handler = AppHandler::new(event_loop.create_proxy());
     event_loop.run_app(&mut handler).unwrap();
 }
 
 #[allow(dead_code)]
 pub fn main() {
     log::info!("Starting Snow Player on Desktop");
-    let mut handler = AppHandler::new();
-    let event_loop = EventLoop::new().unwrap();
+    let event_loop = EventLoop::<AppEvent>::with_user_event().build().unwrap();
+    let mut handler = AppHandler::new(event_loop.create_proxy());
     event_loop.run_app(&mut handler).unwrap();
 }
@@ -0,0 +1,119 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use rayon::prelude::*;
+
+/// An ordered rendering phase. Every registered phase is recorded into its
+/// own `CommandBuffer` in parallel, then the buffers are submitted to the
+/// queue in this order (`Opaque` first, `Overlay` last) so later phases
+/// draw on top of earlier ones.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RenderPhase {
+    Opaque,
+    Transparent,
+    Overlay,
+}
+
+type PassRecorder = dyn Fn(&mut wgpu::RenderPass) + Send + Sync;
+
+struct PhasePass {
+    clear: Option<wgpu::Color>,
+    record: Box<PassRecorder>,
+}
+
+/// Owns the device/queue and a phase-ordered set of render passes.
+///
+/// New draw passes are added with [`Renderer::register_pass`] without
+/// touching the per-frame acquire/submit/present logic in [`Renderer::render`].
+pub struct Renderer {
+    device: Arc<wgpu::Device>,
+    queue: Arc<wgpu::Queue>,
+    passes: BTreeMap<RenderPhase, PhasePass>,
+}
+
+impl Renderer {
+    pub fn new(device: Arc<wgpu::Device>, queue: Arc<wgpu::Queue>) -> Self {
+        Self {
+            device,
+            queue,
+            passes: BTreeMap::new(),
+        }
+    }
+
+    /// Registers (or replaces) the pass recorded for `phase`. If `clear` is
+    /// `Some`, the phase's color attachment is cleared before `record` runs;
+    /// otherwise it loads whatever the previous phase left in the view.
+    pub fn register_pass(
+        &mut self,
+        phase: RenderPhase,
+        clear: Option<wgpu::Color>,
+        record: impl Fn(&mut wgpu::RenderPass) + Send + Sync + 'static,
+    ) {
+        self.passes.insert(
+            phase,
+            PhasePass {
+                clear,
+                record: Box::new(record),
+            },
+        );
+    }
+
+    /// Acquires the next frame, records every registered phase into its own
+    /// `CommandBuffer` in parallel with `rayon`, then submits them in phase
+    /// order in a single `queue.submit`. Callers should reconfigure the
+    /// surface and retry on `SurfaceError::Lost`/`Outdated`.
+    ///
+    /// Only `Opaque` is registered today, so this currently spins up the
+    /// rayon pool to record a single trivial pass; the parallelism earns
+    /// its keep once `Transparent`/`Overlay` passes are added alongside it.
+    pub fn render(
+        &self,
+        surface: &wgpu::Surface,
+        format: wgpu::TextureFormat,
+    ) -> Result<(), wgpu::SurfaceError> {
+        let output = surface.get_current_texture()?;
+        let view = output.texture.create_view(&wgpu::TextureViewDescriptor {
+            format: Some(format),
+            ..Default::default()
+        });
+
+        // `passes` is a `BTreeMap` keyed by `RenderPhase`, so iterating it
+        // yields phases in submission order; `par_iter().map().collect()`
+        // records them concurrently while preserving that order.
+        let buffers: Vec<wgpu::CommandBuffer> = self
+            .passes
+            .par_iter()
+            .map(|(phase, pass)| {
+                let mut encoder =
+                    self.device
+                        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                            label: Some(&format!("{phase:?} phase encoder")),
+                        });
+                {
+                    let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: Some(&format!("{phase:?} phase pass")),
+                        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                            view: &view,
+                            resolve_target: None,
+                            ops: wgpu::Operations {
+                                load: match pass.clear {
+                                    Some(color) => wgpu::LoadOp::Clear(color),
+                                    None => wgpu::LoadOp::Load,
+                                },
+                                store: wgpu::StoreOp::Store,
+                            },
+                        })],
+                        depth_stencil_attachment: None,
+                        ..Default::default()
+                    });
+                    (pass.record)(&mut rpass);
+                }
+                encoder.finish()
+            })
+            .collect();
+
+        self.queue.submit(buffers);
+        output.present();
+        Ok(())
+    }
+}